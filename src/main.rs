@@ -1,12 +1,279 @@
-use std::{io::ErrorKind, str, time::Duration};
+use std::{io::ErrorKind, str, sync::Arc, time::Duration, time::Instant};
 
-use hyper::{body, client::HttpConnector, http::request::Builder, Body, Client, Request};
+use futures::stream::{self, StreamExt};
+use hyper::{
+    body::{self, HttpBody},
+    client::HttpConnector,
+    header::{CONTENT_LENGTH, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED, LOCATION},
+    http::request::Builder,
+    Body, Client, Request, Response, StatusCode, Uri,
+};
 use hyper_tls::HttpsConnector;
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
 use mupdf::{
     pdf::{PdfDocument, PdfObject},
-    TextPageOptions,
+    Outline, TextPageOptions,
 };
-use tokio::{fs, time::sleep};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use tokio::{fs, io::AsyncWriteExt, sync::Mutex, time::sleep};
+
+/// Downloads larger than this are aborted, so a mis-served HTML error page
+/// or a runaway response can't exhaust disk or memory.
+const MAX_DOWNLOAD_SIZE: u64 = 200 * 1024 * 1024;
+
+/// Streams `resp`'s body to `dest_path`, writing through a `{dest_path}.tmp`
+/// file that's atomically renamed into place once the whole body has been
+/// received, and updating `progress` with how much has been downloaded so
+/// far. Aborts if the body exceeds `max_size` bytes.
+async fn stream_download_to_file(
+    resp: Response<Body>,
+    dest_path: &str,
+    max_size: u64,
+    progress: &ProgressBar,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let content_length = resp
+        .headers()
+        .get(CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+    let tmp_path = format!("{}.tmp", dest_path);
+    let mut file = fs::File::create(&tmp_path).await?;
+    let mut body = resp.into_body();
+    let mut downloaded: u64 = 0;
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk?;
+        downloaded += chunk.len() as u64;
+        if downloaded > max_size {
+            drop(file);
+            let _ = fs::remove_file(&tmp_path).await;
+            return Err(Box::new(std::io::Error::new(
+                ErrorKind::Other,
+                format!(
+                    "download of {} exceeded the maximum size of {} bytes",
+                    dest_path, max_size
+                ),
+            )));
+        }
+        file.write_all(&chunk).await?;
+        progress.set_message(match content_length {
+            Some(total) => format!("downloading {} ({}/{} bytes)", dest_path, downloaded, total),
+            None => format!("downloading {} ({} bytes)", dest_path, downloaded),
+        });
+    }
+    file.flush().await?;
+    drop(file);
+    fs::rename(&tmp_path, dest_path).await?;
+    Ok(())
+}
+
+/// Default number of weeks processed concurrently, overridable with the
+/// `MAX_CONCURRENT_WEEKS` environment variable.
+const DEFAULT_MAX_CONCURRENT_WEEKS: usize = 4;
+
+fn max_concurrent_weeks() -> usize {
+    std::env::var("MAX_CONCURRENT_WEEKS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_MAX_CONCURRENT_WEEKS)
+}
+
+/// Enforces a minimum delay between requests across all concurrent tasks,
+/// so bounding concurrency doesn't turn into hammering the upstream servers.
+struct RateLimiter {
+    min_interval: Duration,
+    last_request: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    fn new(min_interval: Duration) -> Self {
+        Self {
+            min_interval,
+            last_request: Mutex::new(Instant::now() - min_interval),
+        }
+    }
+
+    async fn wait_turn(&self) {
+        loop {
+            let mut last_request = self.last_request.lock().await;
+            let elapsed = last_request.elapsed();
+            if elapsed >= self.min_interval {
+                *last_request = Instant::now();
+                return;
+            }
+            let remaining = self.min_interval - elapsed;
+            drop(last_request);
+            sleep(remaining).await;
+        }
+    }
+}
+
+/// Sidecar metadata stored next to a generated fat PDF so the next run can
+/// revalidate it with the origin server instead of assuming it is still
+/// up to date.
+#[derive(Serialize, Deserialize)]
+struct CacheMeta {
+    url: String,
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+fn cache_meta_filename(output_filename: &str) -> String {
+    format!("{}.meta", output_filename)
+}
+
+/// Loads the sidecar cache metadata for `output_filename`, but only if that
+/// PDF is still actually present on disk — otherwise a `304 Not Modified`
+/// would be trusted to mean "keep the existing file" when there is no
+/// existing file to keep (e.g. the PDF was deleted while its sidecar was
+/// left behind), permanently skipping regeneration.
+async fn load_cache_meta(output_filename: &str) -> Option<CacheMeta> {
+    fs::metadata(output_filename).await.ok()?;
+    let bytes = fs::read(cache_meta_filename(output_filename)).await.ok()?;
+    serde_json::from_slice(&bytes).ok()
+}
+
+async fn save_cache_meta(
+    output_filename: &str,
+    meta: &CacheMeta,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let bytes = serde_json::to_vec_pretty(meta)?;
+    fs::write(cache_meta_filename(output_filename), bytes).await?;
+    Ok(())
+}
+
+/// Initial delay before the first retry of a failed request.
+const RETRY_INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+/// Upper bound on the backoff delay, so retries don't end up waiting forever
+/// between attempts.
+const RETRY_MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Sends a request built by `build_req`, retrying with exponential backoff
+/// and jitter on connection errors or 5xx/429 responses, up to
+/// `max_attempts` tries total. `build_req` is called again for each attempt
+/// since a `Request` can't be reused once sent. Status lines are written
+/// through `progress` rather than straight to stdout, since retries can
+/// happen while other weeks' spinners are live.
+async fn fetch_with_retry<F>(
+    client: &Client<HttpsConnector<HttpConnector>>,
+    rate_limiter: &RateLimiter,
+    progress: &ProgressBar,
+    mut build_req: F,
+    max_attempts: u32,
+) -> Result<Response<Body>, Box<dyn std::error::Error + Send + Sync>>
+where
+    F: FnMut() -> Result<Request<Body>, hyper::http::Error>,
+{
+    let mut backoff = RETRY_INITIAL_BACKOFF;
+    for attempt in 1.. {
+        let req = build_req()?;
+        rate_limiter.wait_turn().await;
+        let result = client.request(req).await;
+        let should_retry = match &result {
+            Ok(resp) => resp.status().is_server_error() || resp.status() == StatusCode::TOO_MANY_REQUESTS,
+            Err(_) => true,
+        };
+        if !should_retry || attempt >= max_attempts {
+            return Ok(result?);
+        }
+        let jitter = Duration::from_millis(rand::thread_rng().gen_range(0..250));
+        progress.println(format!(
+            "Request failed (attempt {}/{}), retrying in {:?}...",
+            attempt, max_attempts, backoff
+        ));
+        sleep(backoff + jitter).await;
+        backoff = (backoff * 2).min(RETRY_MAX_BACKOFF);
+    }
+    unreachable!()
+}
+
+/// Maximum number of redirects `fetch_following_redirects` will follow
+/// before giving up.
+const MAX_REDIRECT_HOPS: u32 = 5;
+
+/// Resolves a `Location` header value against the URL it was received in
+/// response to, joining relative locations the same way a browser would.
+/// Handles absolute URLs, protocol-relative URLs (`//other-host/path`), and
+/// both absolute and relative paths.
+fn resolve_location(base: &str, location: &str) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    if location.contains("://") {
+        return Ok(location.to_owned());
+    }
+    let base_uri: Uri = base.parse()?;
+    let scheme = base_uri.scheme_str().ok_or("base URL has no scheme")?;
+    if let Some(rest) = location.strip_prefix("//") {
+        return Ok(format!("{}://{}", scheme, rest));
+    }
+    let authority = base_uri
+        .authority()
+        .ok_or("base URL has no authority")?
+        .as_str();
+    if let Some(absolute_path) = location.strip_prefix('/') {
+        Ok(format!("{}://{}/{}", scheme, authority, absolute_path))
+    } else {
+        let base_path = base_uri.path();
+        let dir_end = base_path.rfind('/').map(|i| i + 1).unwrap_or(0);
+        Ok(format!(
+            "{}://{}{}{}",
+            scheme,
+            authority,
+            &base_path[..dir_end],
+            location
+        ))
+    }
+}
+
+/// Fetches `url`, following `301`/`302`/`303`/`307`/`308` responses that
+/// carry a `Location` header, up to `MAX_REDIRECT_HOPS` times.
+/// `extra_headers` is applied to the request built for every hop, on top of
+/// `fake_browser`'s headers, so callers can attach things like
+/// conditional-request headers. Other 3xx statuses (notably `304 Not
+/// Modified`, used for conditional requests) are returned to the caller
+/// untouched rather than treated as a redirect.
+async fn fetch_following_redirects<F>(
+    client: &Client<HttpsConnector<HttpConnector>>,
+    rate_limiter: &RateLimiter,
+    progress: &ProgressBar,
+    url: &str,
+    max_attempts: u32,
+    extra_headers: F,
+) -> Result<Response<Body>, Box<dyn std::error::Error + Send + Sync>>
+where
+    F: Fn(Builder) -> Builder,
+{
+    let mut current_url = url.to_owned();
+    for _ in 0..=MAX_REDIRECT_HOPS {
+        let resp = fetch_with_retry(
+            client,
+            rate_limiter,
+            progress,
+            || extra_headers(fake_browser(Request::get(&current_url))).body(Body::empty()),
+            max_attempts,
+        )
+        .await?;
+        let is_redirect_hop = matches!(
+            resp.status(),
+            StatusCode::MOVED_PERMANENTLY
+                | StatusCode::FOUND
+                | StatusCode::SEE_OTHER
+                | StatusCode::TEMPORARY_REDIRECT
+                | StatusCode::PERMANENT_REDIRECT
+        );
+        if !is_redirect_hop {
+            return Ok(resp);
+        }
+        let location = resp
+            .headers()
+            .get(LOCATION)
+            .and_then(|v| v.to_str().ok())
+            .ok_or("redirect response is missing a Location header")?;
+        current_url = resolve_location(&current_url, location)?;
+    }
+    Err(Box::new(std::io::Error::new(
+        ErrorKind::Other,
+        format!("too many redirects while fetching {}", url),
+    )))
+}
 
 /// Returns a `Vec` of URLs with the colles' content as a PDF.
 fn parse_week_list(s: &str) -> Vec<String> {
@@ -43,25 +310,30 @@ fn fake_browser(builder: Builder) -> Builder {
 }
 
 async fn fetch_week_list(
-    client: &mut Client<HttpsConnector<HttpConnector>>,
+    client: &Client<HttpsConnector<HttpConnector>>,
+    rate_limiter: &RateLimiter,
+    progress: &ProgressBar,
 ) -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync>> {
     let url = "https://mp1.prepa-carnot.fr/programmes-de-colle/";
-    let req = fake_browser(Request::get(url))
-    .body(Body::empty())?;
-    println!("Fetching colle program index at {}...", url);
-    let resp = client.request(req).await?;
+    progress.println(format!("Fetching colle program index at {}...", url));
+    let resp = fetch_following_redirects(client, rate_limiter, progress, url, 5, |b| b).await?;
     if !resp.status().is_success() {
-        panic!("colle program index response is not successful");
+        return Err(Box::new(std::io::Error::new(
+            ErrorKind::Other,
+            format!("colle program index response at {} is not successful", url),
+        )));
     }
     let body = body::to_bytes(resp.into_body()).await?;
     let body_str = str::from_utf8(&body)?;
     let ol = "<ol>";
-    let ol_start = body_str.find(ol).expect("failed to find week list start");
+    let ol_start = body_str
+        .find(ol)
+        .ok_or("failed to find week list start")?;
     let ol_end = ol_start
         + ol.len()
         + body_str[ol_start + ol.len()..]
             .find("</ol>")
-            .expect("failed to find week list end");
+            .ok_or("failed to find week list end")?;
     let week_list_str = &body_str[ol_start + ol.len()..ol_end];
     Ok(parse_week_list(week_list_str))
 }
@@ -97,7 +369,11 @@ fn extract_exercise_numbers(doc: &PdfDocument) -> Result<Vec<i32>, mupdf::Error>
     Ok(res)
 }
 
-fn merge_pdf_document(dest: &mut PdfDocument, src: &PdfDocument) -> Result<(), mupdf::Error> {
+/// Grafts every page of `src` onto the end of `dest` and returns the page
+/// index of the first page that was inserted, so callers can point an
+/// outline bookmark at it.
+fn merge_pdf_document(dest: &mut PdfDocument, src: &PdfDocument) -> Result<i32, mupdf::Error> {
+    let start_page = dest.page_count()?;
     let page_count = src.page_count()?;
     let mut graft_map = dest.new_graft_map()?;
     for i in 0..page_count {
@@ -124,88 +400,283 @@ fn merge_pdf_document(dest: &mut PdfDocument, src: &PdfDocument) -> Result<(), m
         dest.add_object(&dest_page)?;
         dest.insert_page(dest.page_count()?, &dest_page)?;
     }
-    Ok(())
+    Ok(start_page)
 }
 
+/// Builds a bookmark with no children, pointing at `page`.
+fn leaf_outline(title: String, page: i32) -> Outline {
+    Outline {
+        title,
+        uri: None,
+        page: Some(page as u32),
+        down: Vec::new(),
+        x: 0.0,
+        y: 0.0,
+    }
+}
+
+/// Writes a `/Outlines` tree into `doc`'s catalog: a "Programme" bookmark
+/// pointing at the first page, an "Exercices CCINP" bookmark pointing at
+/// `exercises_start_page`, with one child bookmark per
+/// `(exercise_number, page)` pair in `exercise_pages`. Uses
+/// `PdfDocument::set_outlines` rather than hand-building the dict tree, since
+/// that's the only way to get indirect references for every cross-link
+/// (`Parent`/`First`/`Last`/`Next`/`Prev`) instead of inlined cycles that
+/// would make `doc.save()` recurse forever.
+fn write_outline(
+    doc: &mut PdfDocument,
+    exercises_start_page: i32,
+    exercise_pages: &[(i32, i32)],
+) -> Result<(), mupdf::Error> {
+    let children = exercise_pages
+        .iter()
+        .map(|(number, page)| leaf_outline(format!("Exercice {}", number), *page))
+        .collect();
+    let mut exercises_item = leaf_outline("Exercices CCINP".to_owned(), exercises_start_page);
+    exercises_item.down = children;
+    let toc = vec![leaf_outline("Programme".to_owned(), 0), exercises_item];
+    doc.set_outlines(&toc)
+}
+
+/// Fetches `orig_url`, regenerates the fat PDF at `output_filename` if it
+/// changed, and returns whether it was actually regenerated (`false` means
+/// the existing file was kept because the source PDF was unchanged).
 async fn generate_fat_pdf(
     orig_url: &str,
     output_filename: &str,
-    client: &mut Client<HttpsConnector<HttpConnector>>,
-) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let req = fake_browser(Request::get(orig_url)).body(Body::empty())?;
-    println!("Fetching colle content PDF at {}...", orig_url);
-    let resp = client.request(req).await?;
+    client: &Client<HttpsConnector<HttpConnector>>,
+    rate_limiter: &RateLimiter,
+    progress: &ProgressBar,
+) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+    let cached = load_cache_meta(output_filename)
+        .await
+        .filter(|meta| meta.url == orig_url);
+    progress.set_message(format!("{} - fetching index PDF", orig_url));
+    progress.println(format!("Fetching colle content PDF at {}...", orig_url));
+    let resp = fetch_following_redirects(client, rate_limiter, progress, orig_url, 5, |mut builder| {
+        if let Some(cached) = &cached {
+            if let Some(etag) = &cached.etag {
+                builder = builder.header(IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &cached.last_modified {
+                builder = builder.header(IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+        builder
+    })
+    .await?;
+    if resp.status() == StatusCode::NOT_MODIFIED {
+        progress.println(format!(
+            "Colle content PDF at {} is unchanged, keeping existing {}.",
+            orig_url, output_filename
+        ));
+        return Ok(false);
+    }
     if !resp.status().is_success() {
         return Err(Box::new(std::io::Error::new(
             ErrorKind::Other,
             format!("failed to fetch colle content PDF at {}", orig_url),
         )));
     }
-    let pdf = body::to_bytes(resp.into_body()).await?;
-    let mut doc = match PdfDocument::from_bytes(&pdf) {
+    let etag = resp
+        .headers()
+        .get(ETAG)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_owned);
+    let last_modified = resp
+        .headers()
+        .get(LAST_MODIFIED)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_owned);
+    let download_path = format!("{}.download", output_filename);
+    stream_download_to_file(resp, &download_path, MAX_DOWNLOAD_SIZE, progress).await?;
+    let mut doc = match PdfDocument::open(&download_path) {
         Ok(val) => val,
         Err(err) => {
+            let _ = fs::remove_file(&download_path).await;
             return Err(Box::new(std::io::Error::new(
                 ErrorKind::InvalidData,
                 format!("failed to open colle content PDF: {}", err),
-            )))
+            )));
         }
     };
+    // The download was only staged on disk to avoid buffering it in memory;
+    // once mupdf has it open, the raw source PDF isn't needed anymore.
+    fs::remove_file(&download_path).await?;
+    progress.set_message(format!("{} - extracting exercises", orig_url));
     let exercise_numbers = extract_exercise_numbers(&doc)?;
-    println!("CCINP exercises: {:?}", exercise_numbers);
+    progress.println(format!("CCINP exercises: {:?}", exercise_numbers));
     if !exercise_numbers.is_empty() {
-        let tmp = exercise_numbers
-            .iter()
-            .map(|n| n.to_string())
-            .collect::<Vec<String>>()
-            .join(",");
-        let uri = format!("https://ccinp.mpsi1.fr/{}.pdf", tmp);
-        println!("Fetching CCINP exercises PDF at {}...", uri);
-        let resp = client.get(uri.parse()?).await?;
-        if !resp.status().is_success() {
-            return Err(Box::new(std::io::Error::new(
-                ErrorKind::Other,
-                format!("failed to fetch CCINP exercises PDF at {}", uri),
-            )));
+        let mut exercise_pages = Vec::with_capacity(exercise_numbers.len());
+        for exercise_number in &exercise_numbers {
+            let uri = format!("https://ccinp.mpsi1.fr/{}.pdf", exercise_number);
+            progress.set_message(format!("{} - fetching CCINP exercise {}", orig_url, exercise_number));
+            progress.println(format!(
+                "Fetching CCINP exercise {} PDF at {}...",
+                exercise_number, uri
+            ));
+            let resp = fetch_following_redirects(client, rate_limiter, progress, &uri, 5, |b| b).await?;
+            if !resp.status().is_success() {
+                return Err(Box::new(std::io::Error::new(
+                    ErrorKind::Other,
+                    format!("failed to fetch CCINP exercises PDF at {}", uri),
+                )));
+            }
+            let exercise_download_path =
+                format!("{}.ccinp-{}.download", output_filename, exercise_number);
+            stream_download_to_file(resp, &exercise_download_path, MAX_DOWNLOAD_SIZE, progress)
+                .await?;
+            let exercise_doc = PdfDocument::open(&exercise_download_path)?;
+            progress.set_message(format!("{} - merging", orig_url));
+            // Add the exercise at the end of the document, keeping track of
+            // where it landed so we can bookmark it in the outline below.
+            let start_page = merge_pdf_document(&mut doc, &exercise_doc)?;
+            fs::remove_file(&exercise_download_path).await?;
+            exercise_pages.push((*exercise_number, start_page));
         }
-        let exercises_pdf = body::to_bytes(resp.into_body()).await?;
-        let exercises_doc = PdfDocument::from_bytes(&exercises_pdf)?;
-        // Add the exercises at the end of the document.
-        merge_pdf_document(&mut doc, &exercises_doc)?;
+        let exercises_start_page = exercise_pages[0].1;
+        write_outline(&mut doc, exercises_start_page, &exercise_pages)?;
     }
-    println!("Saving fat PDF to {}...", output_filename);
+    progress.set_message(format!("{} - saving", orig_url));
+    progress.println(format!("Saving fat PDF to {}...", output_filename));
     doc.save(output_filename)?;
-    Ok(())
+    save_cache_meta(
+        output_filename,
+        &CacheMeta {
+            url: orig_url.to_owned(),
+            etag,
+            last_modified,
+        },
+    )
+    .await?;
+    Ok(true)
+}
+
+/// What happened to a single week while generating its fat PDF.
+enum WeekOutcome {
+    Generated,
+    Skipped,
+    Failed(String),
+}
+
+impl WeekOutcome {
+    fn label(&self) -> String {
+        match self {
+            WeekOutcome::Generated => "Generated".to_owned(),
+            WeekOutcome::Skipped => "Skipped".to_owned(),
+            WeekOutcome::Failed(reason) => format!("Failed: {}", reason),
+        }
+    }
+}
+
+struct WeekResult {
+    week: usize,
+    url: String,
+    outcome: WeekOutcome,
+}
+
+/// Prints an aligned table summarizing the outcome of every week.
+fn print_summary(results: &[WeekResult]) {
+    println!();
+    println!("Summary:");
+    let week_width = results
+        .iter()
+        .map(|r| r.week.to_string().len())
+        .max()
+        .unwrap_or(0)
+        .max("week".len());
+    let url_width = results
+        .iter()
+        .map(|r| r.url.len())
+        .max()
+        .unwrap_or(0)
+        .max("url".len());
+    for r in results {
+        println!(
+            "  {:>week_width$}  {:<url_width$}  {}",
+            r.week,
+            r.url,
+            r.outcome.label(),
+            week_width = week_width,
+            url_width = url_width,
+        );
+    }
 }
 
 #[tokio::main(flavor = "current_thread")]
 async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     let https = HttpsConnector::new();
-    let mut client = Client::builder().build::<_, hyper::Body>(https);
-
-    let week_list = fetch_week_list(&mut client).await?;
-    for (i, pdf_url) in week_list.iter().enumerate() {
-        let output_filename = format!("{}.pdf", i + 1);
-        match fs::metadata(&output_filename).await {
-            // The file already exists.
-            Ok(_) => {
-                println!(
-                    "Skipping week {} because file {} already exists.",
-                    output_filename,
-                    i + 1
-                );
-                continue;
+    let client = Client::builder().build::<_, hyper::Body>(https);
+    let rate_limiter = Arc::new(RateLimiter::new(Duration::from_secs(3)));
+    let multi_progress = Arc::new(MultiProgress::new());
+    let spinner_style = ProgressStyle::with_template("{spinner} {msg}")
+        .unwrap()
+        .tick_chars("⠁⠂⠄⡀⢀⠠⠐⠈ ");
+
+    let index_spinner = multi_progress.add(ProgressBar::new_spinner());
+    index_spinner.set_style(spinner_style.clone());
+    index_spinner.enable_steady_tick(Duration::from_millis(100));
+    let week_list = fetch_week_list(&client, &rate_limiter, &index_spinner).await?;
+    index_spinner.finish_and_clear();
+    let overall = multi_progress.add(ProgressBar::new(week_list.len() as u64));
+    overall.set_style(
+        ProgressStyle::with_template("weeks {pos}/{len} {wide_bar}").unwrap(),
+    );
+
+    let max_concurrent = max_concurrent_weeks();
+    let results = stream::iter(week_list.into_iter().enumerate())
+        .map(|(i, pdf_url)| {
+            let client = client.clone();
+            let rate_limiter = Arc::clone(&rate_limiter);
+            let multi_progress = Arc::clone(&multi_progress);
+            let overall = overall.clone();
+            let spinner_style = spinner_style.clone();
+            async move {
+                let output_filename = format!("{}.pdf", i + 1);
+                let spinner = multi_progress.add(ProgressBar::new_spinner());
+                spinner.set_style(spinner_style);
+                spinner.enable_steady_tick(Duration::from_millis(100));
+                spinner.println(format!("Generating fat PDF for week {}...", i + 1));
+                let outcome = match generate_fat_pdf(
+                    &pdf_url,
+                    &output_filename,
+                    &client,
+                    &rate_limiter,
+                    &spinner,
+                )
+                .await
+                {
+                    Ok(true) => WeekOutcome::Generated,
+                    Ok(false) => WeekOutcome::Skipped,
+                    Err(err) => {
+                        spinner.println(format!(
+                            "Failed to generate fat PDF for week {}: {}",
+                            i + 1,
+                            err
+                        ));
+                        WeekOutcome::Failed(err.to_string())
+                    }
+                };
+                spinner.finish_and_clear();
+                overall.inc(1);
+                WeekResult {
+                    week: i + 1,
+                    url: pdf_url,
+                    outcome,
+                }
             }
-            // The file does not exist, so generate it.
-            Err(err) if err.kind() == ErrorKind::NotFound => {}
-            Err(err) => panic!("failed to check metadata of {}: {}", output_filename, err),
-        }
-        println!("Waiting before sending a new request...");
-        sleep(Duration::from_secs(3)).await;
-        println!("Generating fat PDF for week {}...", i + 1);
-        if let Err(err) = generate_fat_pdf(pdf_url, &output_filename, &mut client).await {
-            eprintln!("Failed to generate fat PDF: {}", err);
-        }
+        })
+        .buffer_unordered(max_concurrent)
+        .collect::<Vec<WeekResult>>()
+        .await;
+    overall.finish_and_clear();
+
+    let had_failure = results
+        .iter()
+        .any(|r| matches!(r.outcome, WeekOutcome::Failed(_)));
+    print_summary(&results);
+    if had_failure {
+        std::process::exit(1);
     }
     Ok(())
 }